@@ -0,0 +1,54 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use concordium_rust_sdk::id::types::AccountAddress;
+use serde::{Deserialize, Serialize};
+
+use crate::Transaction;
+
+/// A local on-disk cache of previously fetched transactions, keyed by
+/// account, so incremental runs only need to fetch what's new.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    #[serde(default)]
+    accounts: HashMap<String, Vec<Transaction>>,
+}
+
+impl Cache {
+    /// Loads the cache from `path`, or an empty cache if it doesn't exist yet.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let data = serde_json::to_string(self)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// The highest cached transaction `id` for `account`, used as the
+    /// high-water mark below which a normal (non-refresh) run stops paging,
+    /// since pagination walks from newest to oldest.
+    pub fn highest_id(&self, account: &AccountAddress) -> Option<u64> {
+        self.accounts
+            .get(&account.to_string())
+            .and_then(|txs| txs.iter().map(|tx| tx.id).max())
+    }
+
+    /// The transactions already cached for `account`.
+    pub fn cached(&self, account: &AccountAddress) -> impl Iterator<Item = &Transaction> {
+        self.accounts.get(&account.to_string()).into_iter().flatten()
+    }
+
+    /// Merges freshly fetched transactions into the cache for `account`.
+    pub fn merge(&mut self, account: &AccountAddress, fresh: impl IntoIterator<Item = Transaction>) {
+        let existing = self.accounts.entry(account.to_string()).or_default();
+        existing.extend(fresh);
+        existing.sort_by_key(|tx| tx.id);
+        existing.dedup_by_key(|tx| tx.id);
+    }
+}
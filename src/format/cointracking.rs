@@ -0,0 +1,124 @@
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::{Details, Transaction};
+
+use super::TaxFormat;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CoinTrackingRow {
+    #[serde(rename = "Type")]
+    kind: &'static str,
+    #[serde(rename = "Buy Amount")]
+    buy_amount: Option<f64>,
+    #[serde(rename = "Buy Currency")]
+    buy_currency: Option<&'static str>,
+    #[serde(rename = "Sell Amount")]
+    sell_amount: Option<f64>,
+    #[serde(rename = "Sell Currency")]
+    sell_currency: Option<&'static str>,
+    #[serde(rename = "Fee")]
+    fee: Option<f64>,
+    #[serde(rename = "Fee Currency")]
+    fee_currency: Option<&'static str>,
+    #[serde(rename = "Exchange")]
+    exchange: &'static str,
+    #[serde(rename = "Date")]
+    date: String,
+}
+
+/// The CoinTracking "Custom Import" CSV schema, folding a single CCD
+/// movement and its fee into one buy/sell/fee row.
+pub struct CoinTrackingFormat;
+
+impl TaxFormat for CoinTrackingFormat {
+    type Record = CoinTrackingRow;
+
+    fn headers(&self) -> Vec<&str> {
+        vec![
+            "Type",
+            "Buy Amount",
+            "Buy Currency",
+            "Sell Amount",
+            "Sell Currency",
+            "Fee",
+            "Fee Currency",
+            "Exchange",
+            "Date",
+        ]
+    }
+
+    fn rows(&self, tx: &Transaction) -> anyhow::Result<Vec<CoinTrackingRow>> {
+        // These move no CCD of their own; only the fee is taxable.
+        if matches!(
+            tx.details,
+            Details::UpdateCredentials {}
+                | Details::InitContract {}
+                | Details::Update { .. }
+                | Details::RegisterData {}
+        ) {
+            return Ok(self.fee_row(tx).into_iter().collect());
+        }
+
+        let total = tx.total.context("no amount found")?;
+        let amount = tx.subtotal.unwrap_or(total) as f64 / 1_000_000.0;
+        let fee = tx.cost.map(|cost| cost.micro_ccd as f64 / 1_000_000.0);
+
+        let kind = match tx.details {
+            Details::PaydayAccountReward {}
+            | Details::BakingReward {}
+            | Details::FinalizationReward {}
+            | Details::BlockReward {} => "Mining",
+            _ if amount >= 0.0 => "Deposit",
+            _ => "Withdrawal",
+        };
+
+        let (buy_amount, buy_currency, sell_amount, sell_currency) = if amount >= 0.0 {
+            (Some(amount), Some("CCD"), None, None)
+        } else {
+            (None, None, Some(-amount), Some("CCD"))
+        };
+
+        Ok(vec![CoinTrackingRow {
+            kind,
+            buy_amount,
+            buy_currency,
+            sell_amount,
+            sell_currency,
+            fee,
+            fee_currency: fee.map(|_| "CCD"),
+            exchange: "Concordium",
+            date: tx
+                .block_time
+                .naive_utc()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        }])
+    }
+}
+
+impl CoinTrackingFormat {
+    /// A fee-only row for transactions that move no CCD of their own, or
+    /// `None` if the transaction had no fee either (e.g. a reward-driven
+    /// protocol event).
+    fn fee_row(&self, tx: &Transaction) -> Option<CoinTrackingRow> {
+        let cost = tx.cost?;
+        let fee = cost.micro_ccd as f64 / 1_000_000.0;
+        Some(CoinTrackingRow {
+            kind: "Withdrawal",
+            buy_amount: None,
+            buy_currency: None,
+            sell_amount: Some(0.0),
+            sell_currency: Some("CCD"),
+            fee: Some(fee),
+            fee_currency: Some("CCD"),
+            exchange: "Concordium",
+            date: tx
+                .block_time
+                .naive_utc()
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        })
+    }
+}
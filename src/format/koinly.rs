@@ -0,0 +1,215 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use concordium_rust_sdk::{base::hashes::TransactionHash, common::types::Amount};
+use serde::Serialize;
+
+use crate::{
+    price::PriceOracle,
+    token::{TokenKey, TokenMetadataResolver, TokenTransferEvent},
+    Details, Transaction,
+};
+
+use super::TaxFormat;
+
+#[derive(Debug, Serialize)]
+pub enum KoinlyLabel {
+    Fee,
+    Mining,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KoinlyRow {
+    #[serde(rename = "Koinly Date")]
+    date: String,
+    amount: f64,
+    currency: String,
+    label: Option<KoinlyLabel>,
+    tx_hash: Option<TransactionHash>,
+    description: Option<String>,
+    #[serde(rename = "Net Worth Amount")]
+    net_worth_amount: Option<f64>,
+    #[serde(rename = "Net Worth Currency")]
+    net_worth_currency: Option<String>,
+    // Kept around to look up the historical price after construction; not part of the output.
+    #[serde(skip)]
+    block_time: DateTime<Utc>,
+    // Raw token leg awaiting metadata resolution; not part of the output.
+    #[serde(skip)]
+    pending_token: Option<(TokenKey, i128)>,
+}
+
+impl KoinlyRow {
+    fn new_ccd(
+        date: String,
+        amount: f64,
+        label: Option<KoinlyLabel>,
+        tx_hash: Option<TransactionHash>,
+        description: Option<String>,
+        block_time: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            date,
+            amount,
+            currency: "CCD".to_string(),
+            label,
+            tx_hash,
+            description,
+            net_worth_amount: None,
+            net_worth_currency: None,
+            block_time,
+            pending_token: None,
+        }
+    }
+
+    /// Fills in the net worth columns from the CCD spot price on the day of
+    /// the transaction. Leaves the columns empty if the oracle has no price
+    /// for that day rather than failing the row, and is a no-op for resolved
+    /// token legs, since the oracle only knows the CCD price.
+    pub async fn fill_net_worth(&mut self, oracle: &mut PriceOracle, fiat: &str) -> anyhow::Result<()> {
+        if self.currency != "CCD" {
+            return Ok(());
+        }
+
+        let Some(price) = oracle.price_on(self.block_time.date_naive()).await? else {
+            return Ok(());
+        };
+
+        self.net_worth_amount = Some(self.amount.abs() * price);
+        self.net_worth_currency = Some(fiat.to_string());
+        Ok(())
+    }
+
+    /// Resolves a pending token leg's symbol and decimals, scaling the raw
+    /// amount accordingly. A no-op for rows that aren't a token leg.
+    pub async fn fill_token_metadata(&mut self, resolver: &mut TokenMetadataResolver) -> anyhow::Result<()> {
+        let Some((key, raw_amount)) = self.pending_token.take() else {
+            return Ok(());
+        };
+
+        let metadata = resolver.resolve(&key).await?;
+        self.currency = metadata.symbol;
+        self.amount = raw_amount as f64 / 10f64.powi(metadata.decimals as i32);
+        Ok(())
+    }
+}
+
+/// The Koinly "Universal" custom CSV import schema.
+pub struct KoinlyFormat;
+
+impl TaxFormat for KoinlyFormat {
+    type Record = KoinlyRow;
+
+    fn headers(&self) -> Vec<&str> {
+        vec![
+            "Koinly Date",
+            "Amount",
+            "Currency",
+            "Label",
+            "TxHash",
+            "Description",
+            "Net Worth Amount",
+            "Net Worth Currency",
+        ]
+    }
+
+    fn rows(&self, tx: &Transaction) -> anyhow::Result<Vec<KoinlyRow>> {
+        match &tx.details {
+            // Reward events are taxable income, not a transfer.
+            Details::PaydayAccountReward {}
+            | Details::BakingReward {}
+            | Details::FinalizationReward {}
+            | Details::BlockReward {} => {
+                let total = tx.total.context("no amount found")?;
+                let amount = tx.subtotal.unwrap_or(total) as f64 / 1_000_000.0;
+                Ok(vec![self.row(tx, amount, Some(KoinlyLabel::Mining), None)])
+            }
+
+            // These move no CCD of their own; only the fee is taxable.
+            Details::UpdateCredentials {} | Details::InitContract {} | Details::RegisterData {} => {
+                Ok(self.fee_row(tx).into_iter().collect())
+            }
+
+            // A contract call may additionally move one or more CIS-2/protocol
+            // tokens; the CCD fee leg stays separate from the token legs.
+            Details::Update { token_transfers } => {
+                let mut rows: Vec<KoinlyRow> = token_transfers
+                    .iter()
+                    .map(|transfer| self.token_row(tx, transfer))
+                    .collect();
+                rows.extend(self.fee_row(tx));
+                Ok(rows)
+            }
+
+            Details::TransferWithMemo { memo, .. } => self.transfer_rows(tx, memo.clone()),
+
+            _ => self.transfer_rows(tx, None),
+        }
+    }
+}
+
+impl KoinlyFormat {
+    fn row(
+        &self,
+        tx: &Transaction,
+        amount: f64,
+        label: Option<KoinlyLabel>,
+        description: Option<String>,
+    ) -> KoinlyRow {
+        KoinlyRow::new_ccd(
+            tx.block_time
+                .naive_utc()
+                .format("%Y-%m-%d %H:%M UTC")
+                .to_string(),
+            amount,
+            label,
+            tx.hash,
+            description,
+            tx.block_time,
+        )
+    }
+
+    /// A single leg of a CIS-2/protocol token transfer, awaiting metadata
+    /// resolution to fill in the real symbol and scaled amount.
+    fn token_row(&self, tx: &Transaction, transfer: &TokenTransferEvent) -> KoinlyRow {
+        let mut row = self.row(tx, 0.0, None, None);
+        row.pending_token = Some((transfer.key(), transfer.amount));
+        row
+    }
+
+    fn fee_row(&self, tx: &Transaction) -> Option<KoinlyRow> {
+        let cost = tx.cost?;
+        Some(self.row(
+            tx,
+            -(cost.micro_ccd as f64 / 1_000_000.0),
+            Some(KoinlyLabel::Fee),
+            None,
+        ))
+    }
+
+    /// A regular value transfer plus its fee leg, or just the fee leg if no
+    /// funds actually moved (e.g. a zero-amount transfer).
+    fn transfer_rows(
+        &self,
+        tx: &Transaction,
+        description: Option<String>,
+    ) -> anyhow::Result<Vec<KoinlyRow>> {
+        let total = tx.total.context("no amount found")?;
+        let amount = tx.subtotal.unwrap_or(total) as f64 / 1_000_000.0;
+        let value = self.row(tx, amount, None, description);
+
+        let Some(cost) = tx.cost else {
+            return Ok(vec![value]);
+        };
+
+        let fee = self
+            .fee_row(tx)
+            .expect("cost was just checked to be Some");
+
+        if Amount::from_micro_ccd(total.unsigned_abs()) == cost {
+            // We're not transferring any funds, only paying a fee.
+            return Ok(vec![fee]);
+        }
+        Ok(vec![value, fee])
+    }
+}
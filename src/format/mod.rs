@@ -0,0 +1,55 @@
+mod cointracking;
+mod koinly;
+mod raw;
+
+use std::io;
+
+use serde::Serialize;
+
+pub use cointracking::CoinTrackingFormat;
+pub use koinly::KoinlyFormat;
+pub use raw::RawFormat;
+
+use crate::Transaction;
+
+/// A tax-report schema, turning parsed [`Transaction`]s into CSV rows.
+pub trait TaxFormat {
+    type Record: Serialize;
+
+    /// Column headers, in the order `rows` serializes fields.
+    fn headers(&self) -> Vec<&str>;
+
+    /// The rows a single transaction expands into (e.g. a value leg and a
+    /// fee leg). Transactions this format has nothing to say about can
+    /// return an empty vec.
+    fn rows(&self, tx: &Transaction) -> anyhow::Result<Vec<Self::Record>>;
+
+    /// Writes every transaction's rows as CSV, skipping transactions that
+    /// fail to convert rather than aborting the whole run.
+    fn write<W: io::Write>(&self, transactions: &[Transaction], writer: W) -> anyhow::Result<()> {
+        let records = transactions
+            .iter()
+            .filter_map(|tx| self.rows(tx).ok())
+            .flatten();
+        write_csv(self.headers(), records, writer)
+    }
+}
+
+/// Writes `headers` followed by one serialized record per row.
+pub fn write_csv<R: Serialize>(
+    headers: Vec<&str>,
+    records: impl IntoIterator<Item = R>,
+    writer: impl io::Write,
+) -> anyhow::Result<()> {
+    // We write the header row ourselves, so tell the writer not to derive
+    // (and duplicate) one from the first record's field names.
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    wtr.write_record(headers)?;
+    for record in records {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
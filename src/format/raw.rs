@@ -0,0 +1,69 @@
+use concordium_rust_sdk::base::hashes::TransactionHash;
+use serde::Serialize;
+
+use crate::{Details, Transaction};
+
+use super::TaxFormat;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RawRow {
+    id: u64,
+    #[serde(rename = "Block Time")]
+    block_time: String,
+    #[serde(rename = "Type")]
+    kind: &'static str,
+    tx_hash: Option<TransactionHash>,
+    cost: Option<u64>,
+    subtotal: Option<i64>,
+    total: Option<i64>,
+}
+
+/// A generic dump of a transaction's top-level fields plus its type, for
+/// importers that don't match one of the dedicated schemas. The
+/// type-specific details (transfer accounts, memo, token transfers) are
+/// not included.
+pub struct RawFormat;
+
+impl TaxFormat for RawFormat {
+    type Record = RawRow;
+
+    fn headers(&self) -> Vec<&str> {
+        vec![
+            "Id",
+            "Block Time",
+            "Type",
+            "TxHash",
+            "Cost",
+            "Subtotal",
+            "Total",
+        ]
+    }
+
+    fn rows(&self, tx: &Transaction) -> anyhow::Result<Vec<RawRow>> {
+        let kind = match tx.details {
+            Details::Transfer { .. } => "transfer",
+            Details::TransferWithMemo { .. } => "transferWithMemo",
+            Details::EncryptedAmountTransfer { .. } => "encryptedAmountTransfer",
+            Details::PaydayAccountReward {} => "paydayAccountReward",
+            Details::BakingReward {} => "bakingReward",
+            Details::FinalizationReward {} => "finalizationReward",
+            Details::BlockReward {} => "blockReward",
+            Details::UpdateCredentials {} => "updateCredentials",
+            Details::InitContract {} => "initContract",
+            Details::Update { .. } => "update",
+            Details::RegisterData {} => "registerData",
+            Details::Other {} => "other",
+        };
+
+        Ok(vec![RawRow {
+            id: tx.id,
+            block_time: tx.block_time.to_rfc3339(),
+            kind,
+            tx_hash: tx.hash,
+            cost: tx.cost.map(|cost| cost.micro_ccd),
+            subtotal: tx.subtotal,
+            total: tx.total,
+        }])
+    }
+}
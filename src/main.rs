@@ -1,14 +1,21 @@
-use std::collections::BTreeSet;
+mod cache;
+mod format;
+mod price;
+mod token;
+
+use std::{collections::BTreeSet, fs::File, io, path::PathBuf};
 
-use anyhow::Context;
 use chrono::{DateTime, Utc};
 use clap::{Parser, ValueEnum};
 use concordium_rust_sdk::{
-    base::hashes::TransactionHash,
-    common::types::Amount,
-    id::types::AccountAddress,
+    base::hashes::TransactionHash, common::types::Amount, id::types::AccountAddress,
 };
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Serialize};
+
+use cache::Cache;
+use format::{CoinTrackingFormat, KoinlyFormat, RawFormat, TaxFormat};
+use price::PriceOracle;
+use token::{TokenMetadataResolver, TokenTransferEvent};
 
 const URL: &str = "https://wallet-proxy.mainnet.concordium.software";
 
@@ -23,94 +30,35 @@ struct Args {
     /// The amount of transactions to request per request made to the API.
     #[clap(short = 'l', long = "api-limit", default_value = "100")]
     api_limit: u16,
-    /// The output format. Currently only "koinly" is supported
+    /// The output format.
     #[clap(value_enum, default_value_t = Format::Koinly)]
     format: Format,
+    /// The fiat currency to look up the historical CCD price in (e.g. EUR,
+    /// USD). When set, Koinly rows are annotated with their net worth at the
+    /// time of the transaction.
+    #[clap(long = "fiat")]
+    fiat: Option<String>,
+    /// Where to write the report. Defaults to stdout.
+    #[clap(short = 'o', long = "output")]
+    output: Option<PathBuf>,
+    /// A local cache file of previously fetched transactions. When set,
+    /// subsequent runs only fetch transactions newer than the highest
+    /// cached one per account.
+    #[clap(long = "cache")]
+    cache: Option<PathBuf>,
+    /// Ignore the cache and re-fetch each account's full history.
+    #[clap(long = "refresh")]
+    refresh: bool,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
 enum Format {
     Koinly,
+    CoinTracking,
+    Raw,
 }
 
-#[derive(Debug, Serialize)]
-enum KoinlyLabel {
-    Fee,
-    Mining,
-}
-
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct KoinlyRow {
-    #[serde(rename = "Koinly Date")]
-    date: String,
-    amount: f64,
-    currency: String,
-    label: Option<KoinlyLabel>,
-    tx_hash: Option<TransactionHash>,
-}
-
-impl KoinlyRow {
-    fn new_ccd(
-        date: String,
-        amount: f64,
-        label: Option<KoinlyLabel>,
-        tx_hash: Option<TransactionHash>,
-    ) -> Self {
-        Self {
-            date,
-            amount,
-            currency: "CCD".to_string(),
-            label,
-            tx_hash,
-        }
-    }
-}
-
-impl TryFrom<&Transaction> for Vec<KoinlyRow> {
-    type Error = anyhow::Error;
-
-    fn try_from(tx: &Transaction) -> Result<Self, Self::Error> {
-        let total = tx.total.context("no amount found")?;
-        let amount = tx.subtotal.unwrap_or(total) as f64 / 1_000_000.0;
-        let label = match tx.details {
-            Details::PaydayAccountReward {} => Some(KoinlyLabel::Mining),
-            _ => None,
-        };
-
-        let value = KoinlyRow::new_ccd(
-            tx.block_time
-                .naive_utc()
-                .format("%Y-%m-%d %H:%M UTC")
-                .to_string(),
-            amount,
-            label,
-            tx.hash,
-        );
-
-        let Some(cost) = tx.cost else {
-            return Ok(vec![value]);
-        };
-
-        let fee = KoinlyRow::new_ccd(
-            tx.block_time
-                .naive_utc()
-                .format("%Y-%m-%d %H:%M UTC")
-                .to_string(),
-            -(cost.micro_ccd as f64 / 1_000_000.0),
-            Some(KoinlyLabel::Fee),
-            tx.hash,
-        );
-
-        if Amount::from_micro_ccd(total.unsigned_abs()) == cost {
-            // We're not transferring any funds, only paying a fee.
-            return Ok(vec![fee]);
-        }
-        return Ok(vec![value, fee]);
-    }
-}
-
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "camelCase")]
 enum Details {
     // The addresses are used to figure out if the transfer is internal or not.
@@ -120,35 +68,110 @@ enum Details {
         #[serde(rename = "transferDestination")]
         to: AccountAddress,
     },
-    // The details of other transactions are not of interest for this specific use-case.
+    TransferWithMemo {
+        #[serde(rename = "transferSource")]
+        from: AccountAddress,
+        #[serde(rename = "transferDestination")]
+        to: AccountAddress,
+        memo: Option<String>,
+    },
+    EncryptedAmountTransfer {
+        #[serde(rename = "transferSource")]
+        from: AccountAddress,
+        #[serde(rename = "transferDestination")]
+        to: AccountAddress,
+    },
     PaydayAccountReward {},
+    // Validator/finalizer rewards. Taxed the same way as payday rewards.
+    BakingReward {},
+    FinalizationReward {},
+    BlockReward {},
+    // Contract and chain interactions that move no CCD of their own; only the fee is taxable.
+    UpdateCredentials {},
+    InitContract {},
+    // CIS-2/protocol-level token transfers emitted by the contract call, pre-signed
+    // relative to the queried account the same way `total`/`subtotal` are for CCD.
+    Update {
+        #[serde(default, rename = "events")]
+        token_transfers: Vec<TokenTransferEvent>,
+    },
+    RegisterData {},
     // Catch-all makes sure don't crash on transactions where the details are not of interest.
     #[serde(untagged)]
     Other {},
 }
 
-fn deserialize_micro_ccd<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let opt: Option<String> = Option::deserialize(deserializer)?;
-    opt.map(|s| s.parse::<i64>().map_err(serde::de::Error::custom))
-        .transpose()
+impl Details {
+    /// Source/destination accounts for transfer-like transactions, used to
+    /// detect internal transfers between the configured accounts.
+    fn transfer_accounts(&self) -> Option<(AccountAddress, AccountAddress)> {
+        match self {
+            Details::Transfer { from, to }
+            | Details::TransferWithMemo { from, to, .. }
+            | Details::EncryptedAmountTransfer { from, to } => Some((*from, *to)),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+// The wallet proxy encodes micro CCD amounts as strings so they survive JS's
+// f64-based JSON number handling without losing precision.
+mod micro_ccd {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &Option<i64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value.map(|v| v.to_string()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let opt: Option<String> = Option::deserialize(deserializer)?;
+        opt.map(|s| s.parse::<i64>().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+// The wallet proxy encodes block time as a Unix timestamp in seconds, with a
+// fractional part for sub-second precision.
+mod block_time {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (time.timestamp_millis() as f64 / 1000.0).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let timestamp = f64::deserialize(deserializer)?;
+        chrono::DateTime::from_timestamp_millis((timestamp * 1000.0) as i64)
+            .ok_or_else(|| serde::de::Error::custom("timestamp out of range"))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 struct Transaction {
     #[serde(rename = "transactionHash")]
     hash: Option<TransactionHash>, // Not available for reward types
     // block_hash: BlockHash, // Can be used as a reference when looking up rewards for the receiver
-    #[serde(deserialize_with = "deserialize_block_time")]
+    #[serde(with = "block_time")]
     block_time: DateTime<Utc>,
     details: Details,
     cost: Option<Amount>, // Not available for reward types
-    #[serde(default, deserialize_with = "deserialize_micro_ccd")]
+    #[serde(default, with = "micro_ccd")]
     subtotal: Option<i64>, // Contains signed amount in micro CCD excluding the `cost`
-    #[serde(deserialize_with = "deserialize_micro_ccd")]
+    #[serde(with = "micro_ccd")]
     total: Option<i64>, // Contains signed amount in micro CCD
     id: u64,
 }
@@ -164,13 +187,18 @@ impl Eq for Transaction {}
 
 impl PartialOrd for Transaction {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.block_time.cmp(&other.block_time))
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Transaction {
+    // Tie-break on `id` so distinct transactions sharing a `block_time` (e.g.
+    // multiple reward variants earned in the same block) never compare
+    // `Equal` and collapse into one entry in the `BTreeSet`.
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.block_time.cmp(&other.block_time)
+        self.block_time
+            .cmp(&other.block_time)
+            .then_with(|| self.id.cmp(&other.id))
     }
 }
 
@@ -182,16 +210,6 @@ struct TransactionsResponse {
     transactions: Vec<Transaction>,
 }
 
-fn deserialize_block_time<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let timestamp = f64::deserialize(deserializer)?;
-    let time: DateTime<Utc> = chrono::DateTime::from_timestamp_millis((timestamp * 1000.0) as i64)
-        .expect("Can convert timestamp");
-    Ok(time)
-}
-
 async fn request_transactions(
     account: &AccountAddress,
     limit: u16,
@@ -211,15 +229,43 @@ async fn request_transactions(
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    let mut cache = match &args.cache {
+        Some(path) => Cache::load(path)?,
+        None => Cache::default(),
+    };
     let mut transactions = BTreeSet::new();
 
     for account in &args.accounts {
+        if !args.refresh {
+            transactions.extend(cache.cached(account).cloned());
+        }
+
+        // Pagination always starts at the newest page and walks backwards in
+        // time, so on a normal run we page until we reach a transaction
+        // that's already cached, rather than seeding `from` with the cached
+        // high-water mark (which would ask for transactions *older* than it).
+        let high_water_mark = if args.refresh { None } else { cache.highest_id(account) };
+
         let mut from = None;
+        let mut fetched = Vec::new();
         loop {
             let (res, has_more) = request_transactions(account, args.api_limit, from).await?;
-            transactions.extend(res.transactions.clone());
 
-            if !has_more {
+            let new_transactions: Vec<_> = res
+                .transactions
+                .iter()
+                .take_while(|tx| match high_water_mark {
+                    Some(mark) => tx.id > mark,
+                    None => true,
+                })
+                .cloned()
+                .collect();
+            let reached_cached_history = new_transactions.len() < res.transactions.len();
+
+            fetched.extend(new_transactions.iter().cloned());
+            transactions.extend(new_transactions);
+
+            if reached_cached_history || !has_more {
                 break;
             }
             let Some(tx) = res.transactions.last() else {
@@ -228,20 +274,55 @@ async fn main() -> anyhow::Result<()> {
 
             from = Some(tx.id);
         }
+
+        if args.cache.is_some() {
+            cache.merge(account, fetched);
+        }
+    }
+
+    if let Some(path) = &args.cache {
+        cache.save(path)?;
     }
 
-    println!("pre filter {}", &transactions.len());
-    transactions.retain(|tx| !matches!(tx.details, Details::Transfer { from, to } if args.accounts.contains(&from) && args.accounts.contains(&to)));
-    println!("success {}", &transactions.len());
+    eprintln!("pre filter {}", &transactions.len());
+    transactions.retain(|tx| {
+        !tx.details
+            .transfer_accounts()
+            .is_some_and(|(from, to)| args.accounts.contains(&from) && args.accounts.contains(&to))
+    });
+    eprintln!("success {}", &transactions.len());
+
+    let transactions: Vec<Transaction> = transactions.into_iter().collect();
+
+    let writer: Box<dyn io::Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    match args.format {
+        Format::Koinly => {
+            let mut rows: Vec<_> = transactions
+                .iter()
+                .filter_map(|tx| KoinlyFormat.rows(tx).ok())
+                .flatten()
+                .collect();
+
+            let mut token_resolver = TokenMetadataResolver::new();
+            for row in rows.iter_mut() {
+                row.fill_token_metadata(&mut token_resolver).await?;
+            }
 
-    let formatted: Vec<KoinlyRow> = transactions
-        .iter()
-        .filter_map(|tx| Vec::<KoinlyRow>::try_from(tx).ok())
-        .flatten()
-        .collect();
+            if let Some(fiat) = &args.fiat {
+                let mut oracle = PriceOracle::new(fiat.clone());
+                for row in rows.iter_mut() {
+                    row.fill_net_worth(&mut oracle, fiat).await?;
+                }
+            }
 
-    for row in formatted.iter() {
-        println!("{:?}", row);
+            format::write_csv(KoinlyFormat.headers(), rows, writer)?;
+        }
+        Format::CoinTracking => CoinTrackingFormat.write(&transactions, writer)?,
+        Format::Raw => RawFormat.write(&transactions, writer)?,
     }
 
     Ok(())
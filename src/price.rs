@@ -0,0 +1,86 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde_json::Value;
+
+const COINGECKO_URL: &str = "https://api.coingecko.com/api/v3/coins/concordium/history";
+
+// Cap retries so a sustained rate limit falls back to an empty net-worth
+// column instead of hanging the run forever.
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+
+/// Looks up the historical CCD spot price in a given fiat currency.
+///
+/// Prices are cached per day, so a run touching many transactions on the
+/// same date only hits the upstream API once for that date.
+pub struct PriceOracle {
+    client: reqwest::Client,
+    fiat: String,
+    cache: HashMap<NaiveDate, Option<f64>>,
+}
+
+impl PriceOracle {
+    pub fn new(fiat: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            fiat,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Returns the CCD spot price in the configured fiat currency on `date`,
+    /// or `None` if the date predates listing or the API has no data for it.
+    pub async fn price_on(&mut self, date: NaiveDate) -> anyhow::Result<Option<f64>> {
+        if let Some(cached) = self.cache.get(&date) {
+            return Ok(*cached);
+        }
+
+        let price = self.fetch_price(date).await?;
+        self.cache.insert(date, price);
+        Ok(price)
+    }
+
+    async fn fetch_price(&self, date: NaiveDate) -> anyhow::Result<Option<f64>> {
+        let url = format!("{COINGECKO_URL}?date={}", date.format("%d-%m-%Y"));
+
+        let mut backoff = Duration::from_secs(1);
+        for _ in 0..MAX_RATE_LIMIT_RETRIES {
+            let res = self
+                .client
+                .get(&url)
+                .send()
+                .await
+                .context("failed to reach CoinGecko")?;
+
+            if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                continue;
+            }
+
+            if !res.status().is_success() {
+                // No data for this date (e.g. it predates listing).
+                return Ok(None);
+            }
+
+            let body: Value = res
+                .json()
+                .await
+                .context("failed to parse CoinGecko response")?;
+
+            // CoinGecko's `current_price` keys are always lowercase,
+            // regardless of the case the user passed via `--fiat`.
+            let price = body
+                .get("market_data")
+                .and_then(|m| m.get("current_price"))
+                .and_then(|p| p.get(&self.fiat.to_lowercase()))
+                .and_then(Value::as_f64);
+
+            return Ok(price);
+        }
+
+        // Still rate-limited after the retry budget; leave net worth empty.
+        Ok(None)
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::URL;
+
+/// Identifies a CIS-2 (or native protocol-level) token by the contract
+/// instance and token id it belongs to. Protocol-level tokens use an empty
+/// `token_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenKey {
+    pub contract_index: u64,
+    pub contract_subindex: u64,
+    pub token_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// A single token leg of a contract update, pre-signed relative to the
+/// queried account the same way `Transaction::total`/`subtotal` are for CCD.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenTransferEvent {
+    pub contract_index: u64,
+    pub contract_subindex: u64,
+    pub token_id: String,
+    // Encoded as a string so it survives JSON's f64-based numbers without
+    // losing precision, the same way `Transaction`'s micro CCD fields are.
+    #[serde(with = "token_amount")]
+    pub amount: i128,
+}
+
+impl TokenTransferEvent {
+    pub fn key(&self) -> TokenKey {
+        TokenKey {
+            contract_index: self.contract_index,
+            contract_subindex: self.contract_subindex,
+            token_id: self.token_id.clone(),
+        }
+    }
+}
+
+mod token_amount {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &i128, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value.to_string().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<i128, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse::<i128>().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Resolves CIS-2/protocol token metadata, caching each token's symbol and
+/// decimals so a contract with many transfers is only looked up once.
+pub struct TokenMetadataResolver {
+    client: reqwest::Client,
+    cache: HashMap<TokenKey, TokenMetadata>,
+}
+
+impl TokenMetadataResolver {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub async fn resolve(&mut self, key: &TokenKey) -> anyhow::Result<TokenMetadata> {
+        if let Some(metadata) = self.cache.get(key) {
+            return Ok(metadata.clone());
+        }
+
+        // Fall back to a synthetic symbol rather than failing the row when
+        // the metadata view doesn't resolve (e.g. a non-CIS-2 contract).
+        let metadata = self.fetch_metadata(key).await.unwrap_or_else(|_| TokenMetadata {
+            symbol: format!(
+                "{}-{}-{}",
+                key.contract_index, key.contract_subindex, key.token_id
+            ),
+            decimals: 0,
+        });
+
+        self.cache.insert(key.clone(), metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn fetch_metadata(&self, key: &TokenKey) -> anyhow::Result<TokenMetadata> {
+        // Reads `symbol` and `decimals` straight from the wallet proxy's
+        // `tokenMetadata` response; a metadata URL in it is not followed.
+        let url = format!(
+            "{URL}/v1/contract/{}/{}/tokenMetadata/{}",
+            key.contract_index, key.contract_subindex, key.token_id
+        );
+
+        let body: Value = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("failed to reach token metadata endpoint")?
+            .json()
+            .await
+            .context("failed to parse token metadata response")?;
+
+        let symbol = body
+            .get("symbol")
+            .and_then(Value::as_str)
+            .context("no symbol in token metadata")?
+            .to_string();
+        let decimals = body.get("decimals").and_then(Value::as_u64).unwrap_or(0) as u8;
+
+        Ok(TokenMetadata { symbol, decimals })
+    }
+}